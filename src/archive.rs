@@ -0,0 +1,396 @@
+use crate::deletion::parse_uri;
+use crate::records::{Candidate, Kind};
+use crate::Agent;
+use anyhow::{Context, Result};
+use atrium_api::{
+    app::bsky::{
+        embed::{defs::AspectRatio, images, record_with_media, video},
+        feed::post::{self, RecordEmbedRefs},
+    },
+    com::atproto::{
+        repo::{create_record, get_record},
+        sync::get_blob,
+    },
+    types::{
+        string::{Cid, Datetime, Did, Nsid},
+        BlobRef, TryIntoUnknown, TypedBlobRef, Union, Unknown,
+    },
+    xrpc::error::{Error as XrpcError, XrpcErrorKind},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// Name of the JSON Lines file holding the archived records inside the archive directory.
+const RECORDS_FILE: &str = "records.jsonl";
+
+/// Name of the directory holding the archived blobs inside the archive directory.
+const BLOBS_DIR: &str = "blobs";
+
+/// An image or video embedded in an archived post.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BlobEntry {
+    /// CID of the blob.
+    pub cid: Cid,
+
+    /// MIME type of the blob.
+    pub mime_type: String,
+
+    /// Path of the downloaded blob, relative to the archive directory, or `None` if the blob
+    /// could not be downloaded, e.g. because it was lost in a PDS migration.
+    pub path: Option<String>,
+
+    /// Alt text of the image or video, if any.
+    pub alt: Option<String>,
+
+    /// Aspect ratio of the image or video, if known.
+    pub aspect_ratio: Option<AspectRatio>,
+}
+
+/// A deleted record as saved in the archive.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Entry {
+    /// URI of the deleted record.
+    pub uri: String,
+
+    /// CID of the deleted record, if known.
+    pub cid: Option<Cid>,
+
+    /// Kind of the deleted record.
+    pub kind: Kind,
+
+    /// Creation time of the record.
+    pub created_at: Datetime,
+
+    /// Time the record was indexed by the AppView, if known.
+    pub indexed_at: Option<Datetime>,
+
+    /// Time the record was archived.
+    pub archived_at: Datetime,
+
+    /// The record as stored in the user's repository, if known. Holds the subject of likes
+    /// and reposts.
+    #[serde(default)]
+    pub value: Option<Unknown>,
+
+    /// The post record, or the reposted post record for reposts, if known.
+    pub record: Option<post::RecordData>,
+
+    /// Engagement counts at the time of deletion, if known.
+    pub like_count: Option<i64>,
+    pub repost_count: Option<i64>,
+    pub reply_count: Option<i64>,
+    pub quote_count: Option<i64>,
+
+    /// Images and videos embedded in the post.
+    #[serde(default)]
+    pub blobs: Vec<BlobEntry>,
+}
+
+impl From<&Candidate> for Entry {
+    fn from(candidate: &Candidate) -> Self {
+        let view = candidate.view.as_ref();
+        Self {
+            uri: candidate.uri.clone(),
+            cid: candidate.cid.clone(),
+            kind: candidate.kind,
+            created_at: candidate.created_at.clone(),
+            indexed_at: view.map(|view| view.indexed_at.clone()),
+            archived_at: Datetime::now(),
+            value: candidate.value.clone(),
+            record: candidate.record.clone(),
+            like_count: view.and_then(|view| view.like_count),
+            repost_count: view.and_then(|view| view.repost_count),
+            reply_count: view.and_then(|view| view.reply_count),
+            quote_count: view.and_then(|view| view.quote_count),
+            blobs: vec![],
+        }
+    }
+}
+
+/// An archive directory where records are saved before being deleted.
+pub struct Archive {
+    path: PathBuf,
+    records: File,
+}
+
+impl Archive {
+    /// Open the archive at `path`, creating the directory if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        std::fs::create_dir_all(path.join(BLOBS_DIR)).context(format!(
+            "Failed to create archive directory {}",
+            path.display()
+        ))?;
+        let records_path = path.join(RECORDS_FILE);
+        let records = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&records_path)
+            .context(format!("Failed to open archive {}", records_path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            records,
+        })
+    }
+
+    /// Append `candidate` to the archive, downloading the images and videos of own posts.
+    pub async fn save(&mut self, agent: &Agent, did: &Did, candidate: &Candidate) -> Result<()> {
+        let mut entry = Entry::from(candidate);
+        if candidate.kind == Kind::Post {
+            if let Some(record) = &candidate.record {
+                for blob in embedded_blobs(record) {
+                    entry.blobs.push(self.download(agent, did, blob).await?);
+                }
+            }
+        }
+
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        self.records
+            .write_all(line.as_bytes())
+            .context(format!("Failed to archive {}", candidate.uri))
+    }
+
+    /// Download `blob` from the user's repository into the archive.
+    ///
+    /// Blobs that cannot be downloaded are recorded without a path, so that the post can
+    /// still be archived and deleted. Only failing to write the blob is an error.
+    async fn download(&self, agent: &Agent, did: &Did, blob: EmbeddedBlob) -> Result<BlobEntry> {
+        let data = agent
+            .api
+            .com
+            .atproto
+            .sync
+            .get_blob(
+                get_blob::ParametersData {
+                    cid: blob.cid.clone(),
+                    did: did.clone(),
+                }
+                .into(),
+            )
+            .await;
+        let path = match data {
+            Ok(data) => {
+                let path = format!("{BLOBS_DIR}/{}", blob.cid.as_ref());
+                std::fs::write(self.path.join(&path), data)
+                    .context(format!("Failed to archive blob {}", blob.cid.as_ref()))?;
+                Some(path)
+            }
+            Err(e) => {
+                eprintln!(
+                    "Could not download blob {}, archiving the post without it: {e}",
+                    blob.cid.as_ref()
+                );
+                None
+            }
+        };
+        Ok(BlobEntry {
+            cid: blob.cid,
+            mime_type: blob.mime_type,
+            path,
+            alt: blob.alt,
+            aspect_ratio: blob.aspect_ratio,
+        })
+    }
+}
+
+/// A blob referenced by an image or video embed.
+struct EmbeddedBlob {
+    cid: Cid,
+    mime_type: String,
+    alt: Option<String>,
+    aspect_ratio: Option<AspectRatio>,
+}
+
+impl EmbeddedBlob {
+    fn new(blob: &BlobRef, alt: Option<String>, aspect_ratio: Option<AspectRatio>) -> Option<Self> {
+        let (cid, mime_type) = match blob {
+            BlobRef::Typed(TypedBlobRef::Blob(blob)) => {
+                (Cid::new(blob.r#ref.0), blob.mime_type.clone())
+            }
+            BlobRef::Untyped(blob) => (blob.cid.parse().ok()?, blob.mime_type.clone()),
+        };
+        Some(Self {
+            cid,
+            mime_type,
+            alt,
+            aspect_ratio,
+        })
+    }
+}
+
+/// Blobs of the images and videos embedded in a post.
+fn embedded_blobs(record: &post::RecordData) -> Vec<EmbeddedBlob> {
+    let images = |embed: &images::Main| -> Vec<EmbeddedBlob> {
+        embed
+            .images
+            .iter()
+            .filter_map(|image| {
+                EmbeddedBlob::new(
+                    &image.image,
+                    Some(image.alt.clone()),
+                    image.aspect_ratio.clone(),
+                )
+            })
+            .collect()
+    };
+    let video = |embed: &video::Main| -> Vec<EmbeddedBlob> {
+        EmbeddedBlob::new(&embed.video, embed.alt.clone(), embed.aspect_ratio.clone())
+            .into_iter()
+            .collect()
+    };
+
+    match &record.embed {
+        Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedImagesMain(embed))) => images(embed),
+        Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedVideoMain(embed))) => video(embed),
+        Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedRecordWithMediaMain(embed))) => {
+            match &embed.media {
+                Union::Refs(record_with_media::MainMediaRefs::AppBskyEmbedImagesMain(media)) => {
+                    images(media)
+                }
+                Union::Refs(record_with_media::MainMediaRefs::AppBskyEmbedVideoMain(media)) => {
+                    video(media)
+                }
+                _ => vec![],
+            }
+        }
+        _ => vec![],
+    }
+}
+
+/// Read every entry saved in the archive at `path`.
+///
+/// Malformed lines are skipped with a warning, since a run killed while archiving leaves a
+/// truncated last line behind.
+pub fn read_entries(path: impl AsRef<Path>) -> Result<Vec<Entry>> {
+    let records_path = path.as_ref().join(RECORDS_FILE);
+    let file = File::open(&records_path)
+        .context(format!("Failed to open archive {}", records_path.display()))?;
+
+    let mut entries = vec![];
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(entry) = serde_json::from_str(&line) else {
+            eprintln!(
+                "Ignoring malformed line {} of {}",
+                number + 1,
+                records_path.display()
+            );
+            continue;
+        };
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Re-create the archived post `entry` under its original URI, re-uploading its blobs from
+/// the archive at `path`.
+///
+/// Keeping the original URI keeps the replies and quotes of other restored posts pointing at
+/// it. Returns `false` without doing anything if the post already exists.
+pub async fn restore(
+    agent: &Agent,
+    did: &Did,
+    path: impl AsRef<Path>,
+    entry: &Entry,
+) -> Result<bool> {
+    let record = entry
+        .record
+        .clone()
+        .context(format!("No record archived for {}", entry.uri))?;
+    if !entry.uri.starts_with(&format!("at://{}/", did.as_str())) {
+        anyhow::bail!("{} does not belong to the logged in user", entry.uri);
+    }
+    let (collection, rkey) = parse_uri(&entry.uri)?;
+    if exists(agent, did, &collection, &rkey).await? {
+        return Ok(false);
+    }
+
+    // Blobs are content addressed, so uploading the same data again makes the original
+    // blob references in the record valid.
+    for blob in &entry.blobs {
+        let blob_path = blob.path.as_ref().context(format!(
+            "Blob {} was not archived and cannot be restored",
+            blob.cid.as_ref()
+        ))?;
+        let data = std::fs::read(path.as_ref().join(blob_path))
+            .context(format!("Failed to read archived blob {blob_path}"))?;
+        let output = agent
+            .api
+            .com
+            .atproto
+            .repo
+            .upload_blob(data)
+            .await
+            .context(format!("Failed to upload blob {}", blob.cid.as_ref()))?;
+        let uploaded = match output.data.blob {
+            BlobRef::Typed(TypedBlobRef::Blob(uploaded)) => Cid::new(uploaded.r#ref.0),
+            BlobRef::Untyped(uploaded) => uploaded.cid.parse()?,
+        };
+        if uploaded != blob.cid {
+            anyhow::bail!(
+                "Uploaded blob {} does not match archived blob {}",
+                uploaded.as_ref(),
+                blob.cid.as_ref()
+            );
+        }
+    }
+
+    agent
+        .api
+        .com
+        .atproto
+        .repo
+        .create_record(
+            create_record::InputData {
+                collection,
+                record: post::Record::from(record).try_into_unknown()?,
+                repo: did.clone().into(),
+                rkey: Some(rkey),
+                swap_commit: None,
+                validate: None,
+            }
+            .into(),
+        )
+        .await
+        .context(format!("Failed to restore {}", entry.uri))?;
+    Ok(true)
+}
+
+/// Whether the record `rkey` of `collection` exists in the repository of `did`.
+async fn exists(agent: &Agent, did: &Did, collection: &Nsid, rkey: &str) -> Result<bool> {
+    let output = agent
+        .api
+        .com
+        .atproto
+        .repo
+        .get_record(
+            get_record::ParametersData {
+                cid: None,
+                collection: collection.clone(),
+                repo: did.clone().into(),
+                rkey: rkey.to_string(),
+            }
+            .into(),
+        )
+        .await;
+    match output {
+        Ok(_) => Ok(true),
+        Err(XrpcError::XrpcResponse(error))
+            if matches!(
+                error.error,
+                Some(XrpcErrorKind::Custom(get_record::Error::RecordNotFound(_)))
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(error) => Err(error).context(format!("Failed to look up record {rkey}")),
+    }
+}
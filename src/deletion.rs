@@ -0,0 +1,114 @@
+use crate::{ratelimit, settings::ErrorPolicy, Agent};
+use anyhow::{Context, Result};
+use atrium_api::{
+    com::atproto::repo::apply_writes,
+    types::string::{AtIdentifier, Did, Nsid, RecordKey},
+};
+
+/// Maximum number of writes accepted by a single `com.atproto.repo.applyWrites` call.
+pub const APPLY_WRITES_LIMIT: usize = 200;
+
+/// The result of deleting a single record.
+pub enum Outcome {
+    Deleted,
+    Failed(anyhow::Error),
+}
+
+/// Split an `at://` URI into its collection and record key.
+pub fn parse_uri(uri: &str) -> Result<(Nsid, String)> {
+    let parts: Vec<_> = uri
+        .strip_prefix("at://")
+        .context(format!("Invalid AT URI {uri}"))?
+        .splitn(3, '/')
+        .collect();
+    let [_, collection, rkey] = parts[..] else {
+        anyhow::bail!("Invalid AT URI {uri}");
+    };
+    let collection = collection
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Invalid collection in {uri}: {e}"))?;
+    let rkey = rkey
+        .parse::<RecordKey>()
+        .map_err(|e| anyhow::anyhow!("Invalid record key in {uri}: {e}"))?;
+    Ok((collection, rkey.into()))
+}
+
+/// Delete all `uris` from the repository of `did` in a single `applyWrites` call.
+async fn apply_deletes(agent: &Agent, did: &Did, uris: &[String]) -> Result<()> {
+    let writes = uris
+        .iter()
+        .map(|uri| {
+            let (collection, rkey) = parse_uri(uri)?;
+            Ok(apply_writes::InputWritesItem::Delete(Box::new(
+                apply_writes::DeleteData { collection, rkey }.into(),
+            )))
+        })
+        .collect::<Result<_>>()?;
+    agent
+        .api
+        .com
+        .atproto
+        .repo
+        .apply_writes(
+            apply_writes::InputData {
+                repo: AtIdentifier::Did(did.clone()),
+                swap_commit: None,
+                validate: None,
+                writes,
+            }
+            .into(),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Delete `uris`, which must fit in a single batch, from the repository of `did`.
+///
+/// The records are deleted with one `applyWrites` call. If it fails, each record is deleted
+/// on its own so that a single bad record does not prevent the others from being deleted,
+/// retrying up to `retries` times. Returns the outcome for each URI, in order. With the
+/// `abort` policy, deletion stops at the first failure and the remaining URIs have no outcome.
+pub async fn delete_batch(
+    agent: &Agent,
+    did: &Did,
+    uris: &[String],
+    policy: ErrorPolicy,
+    retries: u32,
+) -> Vec<Outcome> {
+    debug_assert!(uris.len() <= APPLY_WRITES_LIMIT);
+    match apply_deletes(agent, did, uris).await {
+        Ok(()) => return uris.iter().map(|_| Outcome::Deleted).collect(),
+        Err(e) => eprintln!("Batch deletion failed, deleting records one by one: {e:#}"),
+    }
+
+    let mut outcomes = Vec::with_capacity(uris.len());
+    for uri in uris {
+        let outcome = delete_one(agent, uri, retries).await;
+        let failed = matches!(outcome, Outcome::Failed(_));
+        outcomes.push(outcome);
+        if failed && policy == ErrorPolicy::Abort {
+            break;
+        }
+    }
+    outcomes
+}
+
+/// Delete the record at `uri`, retrying up to `retries` times.
+async fn delete_one(agent: &Agent, uri: &str, retries: u32) -> Outcome {
+    let mut retry = 0;
+    loop {
+        match agent.delete_record(uri).await {
+            Ok(_) => return Outcome::Deleted,
+            Err(e) if retry >= retries => return Outcome::Failed(e.into()),
+            Err(e) => {
+                let delay = ratelimit::backoff(retry);
+                eprintln!(
+                    "Failed to delete {uri} ({e}), retrying in {}ms",
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                retry += 1;
+            }
+        }
+    }
+}
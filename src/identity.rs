@@ -0,0 +1,80 @@
+use crate::ratelimit::RateLimitedClient;
+use anyhow::{Context, Result};
+use atrium_api::{
+    agent::{store::SessionStore, Session},
+    com::atproto::identity::resolve_handle,
+    did_doc::DidDocument,
+    types::{
+        string::{AtIdentifier, Did},
+        TryFromUnknown,
+    },
+    xrpc::{http::Request, HttpClient},
+};
+use bsky_sdk::BskyAgent;
+
+/// Directory serving the documents of `did:plc` identifiers.
+const PLC_DIRECTORY: &str = "https://plc.directory";
+
+/// Find the endpoint of the PDS hosting the account of `identifier`.
+///
+/// Handles are resolved to a DID through the agent's current service, then the PDS is read
+/// from the DID document. Returns `None` for identifiers that cannot be resolved, such as
+/// email addresses.
+pub async fn resolve_pds<S>(
+    agent: &BskyAgent<RateLimitedClient, S>,
+    identifier: &str,
+) -> Result<Option<String>>
+where
+    S: SessionStore + Send + Sync,
+{
+    let did = match identifier.parse::<AtIdentifier>() {
+        Ok(AtIdentifier::Did(did)) => did,
+        Ok(AtIdentifier::Handle(handle)) => {
+            agent
+                .api
+                .com
+                .atproto
+                .identity
+                .resolve_handle(resolve_handle::ParametersData { handle }.into())
+                .await
+                .context(format!("Failed to resolve handle {identifier}"))?
+                .data
+                .did
+        }
+        Err(_) => return Ok(None),
+    };
+    did_document(&did)
+        .await?
+        .get_pds_endpoint()
+        .map(Some)
+        .context(format!("No PDS in the DID document of {}", did.as_str()))
+}
+
+/// Endpoint of the PDS in the DID document of a session, if any.
+pub fn session_pds(session: &Session) -> Option<String> {
+    let did_doc = DidDocument::try_from_unknown(session.did_doc.clone()?).ok()?;
+    did_doc.get_pds_endpoint()
+}
+
+/// Fetch the DID document of a `did:plc` or `did:web` identifier.
+async fn did_document(did: &Did) -> Result<DidDocument> {
+    let url = if let Some(host) = did.as_str().strip_prefix("did:web:") {
+        // Ports are percent-encoded in `did:web` identifiers.
+        format!("https://{}/.well-known/did.json", host.replace("%3A", ":"))
+    } else if did.as_str().starts_with("did:plc:") {
+        format!("{PLC_DIRECTORY}/{}", did.as_str())
+    } else {
+        anyhow::bail!("Unsupported DID method in {}", did.as_str());
+    };
+
+    let request = Request::get(&url).body(Vec::new())?;
+    let response = RateLimitedClient::new(&url)
+        .send_http(request)
+        .await
+        .map_err(|e| anyhow::anyhow!(e))
+        .context(format!("Failed to fetch {url}"))?;
+    if !response.status().is_success() {
+        anyhow::bail!("Failed to fetch {url}: {}", response.status());
+    }
+    serde_json::from_slice(response.body()).context(format!("Invalid DID document at {url}"))
+}
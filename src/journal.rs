@@ -0,0 +1,109 @@
+use crate::deletion::Outcome;
+use crate::records::Candidate;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+/// An entry of the progress journal.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum Event {
+    /// A record was selected for deletion by `rule`.
+    Planned {
+        candidate: Box<Candidate>,
+        rule: String,
+    },
+
+    /// A record was deleted.
+    Deleted { uri: String },
+
+    /// A record could not be deleted.
+    Failed { uri: String, reason: String },
+}
+
+/// A JSON Lines journal of the progress of a deletion run, used to resume interrupted runs.
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    /// Start a new journal at `path` for deleting `plan`, replacing any previous journal.
+    pub fn create(path: impl AsRef<Path>, plan: &[(Candidate, String)]) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).context(format!("Failed to create journal {}", path.display()))?;
+        let mut journal = Self { file };
+        for (candidate, rule) in plan {
+            journal.write(&Event::Planned {
+                candidate: Box::new(candidate.clone()),
+                rule: rule.clone(),
+            })?;
+        }
+        Ok(journal)
+    }
+
+    /// Open the journal at `path` and return the planned records that were not deleted yet.
+    pub fn resume(path: impl AsRef<Path>) -> Result<(Self, Vec<(Candidate, String)>)> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).context(format!("Failed to open journal {}", path.display()))?;
+
+        let mut planned = vec![];
+        let mut deleted = HashSet::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // A run killed while writing leaves a truncated last line behind; skip it.
+            let Ok(event) = serde_json::from_str(&line) else {
+                eprintln!(
+                    "Ignoring malformed line {} of {}",
+                    number + 1,
+                    path.display()
+                );
+                continue;
+            };
+            match event {
+                Event::Planned { candidate, rule } => planned.push((*candidate, rule)),
+                Event::Deleted { uri } => {
+                    deleted.insert(uri);
+                }
+                // Failed records are retried.
+                Event::Failed { .. } => {}
+            }
+        }
+        planned.retain(|(candidate, _)| !deleted.contains(&candidate.uri));
+
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .context(format!("Failed to open journal {}", path.display()))?;
+        Ok((Self { file }, planned))
+    }
+
+    /// Record the outcome of deleting `uri`.
+    pub fn record(&mut self, uri: &str, outcome: &Outcome) -> Result<()> {
+        let uri = uri.to_string();
+        self.write(&match outcome {
+            Outcome::Deleted => Event::Deleted { uri },
+            Outcome::Failed(e) => Event::Failed {
+                uri,
+                reason: format!("{e:#}"),
+            },
+        })
+    }
+
+    fn write(&mut self, event: &Event) -> Result<()> {
+        let mut line = serde_json::to_string(event)?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .context("Failed to write journal")
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Command line client of the Secret Service API, shipped with libsecret.
+const SECRET_TOOL: &str = "secret-tool";
+
+/// Value of the `service` attribute of the secrets stored by this tool.
+const SERVICE: &str = env!("CARGO_PKG_NAME");
+
+/// Store `secret` under `item` in the Secret Service keyring.
+fn store_item(item: &str, secret: &str) -> Result<()> {
+    let mut child = Command::new(SECRET_TOOL)
+        .args(["store", "--label", &format!("{SERVICE} {item}")])
+        .args(["service", SERVICE, "item", item])
+        .stdin(Stdio::piped())
+        .spawn()
+        .context(format!(
+            "Failed to run {SECRET_TOOL}, is libsecret installed?"
+        ))?;
+    child
+        .stdin
+        .take()
+        .context("Failed to open the standard input of secret-tool")?
+        .write_all(secret.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!(
+            "Failed to store the {item} in the keyring: {SECRET_TOOL} exited with {status}"
+        );
+    }
+    Ok(())
+}
+
+/// Look up the secret stored under `item` in the Secret Service keyring, if any.
+///
+/// Fails if the keyring cannot be reached, e.g. when it is locked or no session bus is set.
+fn lookup_item(item: &str) -> Result<Option<String>> {
+    let output = Command::new(SECRET_TOOL)
+        .args(["lookup", "service", SERVICE, "item", item])
+        .output()
+        .context(format!(
+            "Failed to run {SECRET_TOOL}, is libsecret installed?"
+        ))?;
+    if output.status.success() {
+        return Ok(Some(
+            String::from_utf8(output.stdout).context("Invalid secret in the keyring")?,
+        ));
+    }
+
+    // secret-tool exits with an error without printing anything if the secret is missing,
+    // and explains what went wrong on the standard error otherwise.
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.trim().is_empty() {
+        return Ok(None);
+    }
+    anyhow::bail!(
+        "Failed to look up the {item} in the keyring: {}",
+        stderr.trim()
+    )
+}
+
+/// Store the identifier and app password of the user in the keyring.
+pub fn store(identifier: &str, app_password: &str) -> Result<()> {
+    store_item("identifier", identifier)?;
+    store_item("app-password", app_password)
+}
+
+/// Load the identifier stored by [`store`], if any.
+pub fn identifier() -> Result<Option<String>> {
+    lookup_item("identifier")
+}
+
+/// Load the identifier and app password stored by [`store`], if any.
+pub fn load() -> Result<Option<(String, String)>> {
+    let Some(identifier) = identifier()? else {
+        return Ok(None);
+    };
+    let app_password = lookup_item("app-password")?.context(format!(
+        "No app password in the keyring for {identifier}, run the login command again"
+    ))?;
+    Ok(Some((identifier, app_password)))
+}
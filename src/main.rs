@@ -1,17 +1,33 @@
+mod archive;
+mod deletion;
+mod identity;
+mod journal;
+mod keyring;
+mod ratelimit;
+mod records;
+mod report;
+mod rules;
+mod session;
+mod settings;
+
 use anyhow::{Context, Result};
 use atrium_api::{
-    app::bsky::feed::get_author_feed::{Parameters, ParametersData},
-    types::{
-        string::{AtIdentifier::Did, Datetime},
-        TryFromUnknown,
-    },
+    agent::store::{MemorySessionStore, SessionStore},
+    com::atproto::server::create_session,
+    types::string::Did,
+    xrpc::error::{Error as XrpcError, XrpcErrorKind},
+};
+use bsky_sdk::{
+    agent::{config::Config, BskyAgentBuilder},
+    BskyAgent,
 };
-use bsky_sdk::api::app::bsky::feed::post::RecordData;
-use bsky_sdk::BskyAgent;
-use clap::{Parser, Subcommand};
-use config::{Config, File};
-use dialoguer::{theme::ColorfulTheme, Confirm};
-use serde::Deserialize;
+use clap::{Args, Parser, Subcommand};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password};
+use settings::{ErrorPolicy, Settings, Source};
+use std::{collections::HashSet, path::PathBuf};
+
+/// The BlueSky agent used by all commands.
+type Agent = BskyAgent<ratelimit::RateLimitedClient, session::FileSessionStore>;
 
 #[derive(Parser)]
 #[command(version, about)]
@@ -20,6 +36,10 @@ struct Opts {
     #[clap(short, long)]
     yes: bool,
 
+    /// Sign-in code emailed to accounts with two-factor authentication. Prompted for if needed.
+    #[clap(long, global = true)]
+    auth_factor_token: Option<String>,
+
     #[clap(subcommand)]
     command: Command,
 }
@@ -27,142 +47,469 @@ struct Opts {
 #[derive(Subcommand)]
 enum Command {
     /// Delete posts from a user following the configuration file.
-    Delete {
+    Delete(DeleteArgs),
+
+    /// Verify the user's credentials and store them in the keyring.
+    Login {
+        /// URL of the PDS to log in to, like `authentication.service`. Resolved from the
+        /// identifier if not given.
+        #[clap(long)]
+        service: Option<String>,
+    },
+
+    /// Re-create posts saved in the archive of the configuration file.
+    Restore {
         /// Configuration file.
         #[clap(value_parser)]
         config: String,
+
+        /// URIs of the archived posts to restore. All archived posts are restored if none is given.
+        #[clap(long = "uri")]
+        uris: Vec<String>,
     },
 }
 
-#[derive(Deserialize, Debug)]
-struct Authentication {
-    /// BlueSky identifier.
-    identifier: String,
+#[derive(Args)]
+struct DeleteArgs {
+    /// Configuration file.
+    #[clap(value_parser)]
+    config: String,
+
+    /// Number of records to request per page.
+    #[clap(long, default_value_t = 100, value_parser = clap::value_parser!(u8).range(1..=100))]
+    page_size: u8,
 
-    /// BlueSky app password from <https://bsky.app/settings/app-password>.
-    app_password: String,
+    /// Print the records that would be deleted and exit without deleting them.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Progress journal file. Defaults to the configuration file with a `.journal` suffix.
+    #[clap(long)]
+    journal: Option<String>,
+
+    /// Continue an interrupted run from its journal instead of fetching records again.
+    #[clap(long, conflicts_with = "dry_run")]
+    resume: bool,
 }
 
-#[derive(Deserialize, Debug)]
-struct Delete {
-    /// Minimum age of a post to be considered for deletion.
-    #[serde(deserialize_with = "duration_str::deserialize_duration_chrono")]
-    minimum_age: chrono::Duration,
+/// Ask the user for confirmation unless `yes` is set.
+fn confirm(yes: bool) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    let theme = ColorfulTheme::default();
+    let prompt = Confirm::with_theme(&theme).with_prompt("Do you want to proceed?");
+    Ok(prompt.interact()?)
 }
 
-#[derive(Deserialize, Debug)]
-struct Rules {
-    /// When to delete posts.
-    delete: Delete,
+/// Build an agent that is not logged in yet and keeps its session in `store`.
+async fn build_agent<S>(store: S) -> Result<BskyAgent<ratelimit::RateLimitedClient, S>>
+where
+    S: SessionStore + Send + Sync,
+{
+    let client = ratelimit::RateLimitedClient::new(Config::default().endpoint);
+    Ok(BskyAgentBuilder::new(client).store(store).build().await?)
 }
 
-#[derive(Deserialize, Debug)]
-struct Settings {
-    /// Authentication settings.
-    authentication: Authentication,
+/// Get the identifier and app password from the settings, or from the keyring if none is configured.
+fn credentials(settings: &Settings) -> Result<(String, String)> {
+    let authentication = &settings.authentication;
+    match (authentication.identifier(), authentication.app_password()?) {
+        (Some(identifier), Some(app_password)) => Ok((identifier, app_password)),
+        (None, None) => keyring::load()?.context(
+            "No credentials: add an authentication section to the configuration file \
+             or run the login command",
+        ),
+        (None, Some(_)) => {
+            anyhow::bail!("No identifier: set authentication.identifier or BSKY_IDENTIFIER")
+        }
+        (Some(_), None) => anyhow::bail!(
+            "No app password: set one of authentication.app_password, app_password_env, \
+             app_password_file, app_password_command or BSKY_APP_PASSWORD"
+        ),
+    }
+}
 
-    /// Rules for deleting or keeping posts.
-    rules: Rules,
+/// Find the PDS of `identifier`, falling back to the default service if it cannot be resolved.
+async fn resolve_service<S>(
+    agent: &BskyAgent<ratelimit::RateLimitedClient, S>,
+    identifier: &str,
+) -> String
+where
+    S: SessionStore + Send + Sync,
+{
+    match identity::resolve_pds(agent, identifier).await {
+        Ok(Some(endpoint)) => endpoint,
+        Ok(None) => Config::default().endpoint,
+        Err(e) => {
+            eprintln!("Could not find the PDS of {identifier}, using the default service: {e:#}");
+            Config::default().endpoint
+        }
+    }
 }
 
-impl Settings {
-    pub fn from_file(path: &str) -> Result<Self> {
-        Config::builder()
-            .add_source(File::with_name(path))
-            .build()
-            .context(format!("Failed to build config from {path}"))?
-            .try_deserialize()
-            .context(format!("Failed to deserialize config from {path}"))
+/// Create a session with the app password, asking for the emailed sign-in code if the account
+/// requires one and `auth_factor_token` is not given.
+async fn create_session<S>(
+    agent: &BskyAgent<ratelimit::RateLimitedClient, S>,
+    identifier: &str,
+    app_password: &str,
+    auth_factor_token: Option<&str>,
+) -> Result<()>
+where
+    S: SessionStore + Send + Sync,
+{
+    let mut auth_factor_token = auth_factor_token.map(str::to_string);
+    loop {
+        let result = agent
+            .api
+            .com
+            .atproto
+            .server
+            .create_session(
+                create_session::InputData {
+                    allow_takendown: None,
+                    auth_factor_token: auth_factor_token.clone(),
+                    identifier: identifier.to_string(),
+                    password: app_password.to_string(),
+                }
+                .into(),
+            )
+            .await;
+        match result {
+            Ok(session) => {
+                agent.resume_session(session).await?;
+                return Ok(());
+            }
+            Err(XrpcError::XrpcResponse(error))
+                if auth_factor_token.is_none()
+                    && matches!(
+                        error.error,
+                        Some(XrpcErrorKind::Custom(
+                            create_session::Error::AuthFactorTokenRequired(_)
+                        ))
+                    ) =>
+            {
+                let theme = ColorfulTheme::default();
+                let token: String = Input::with_theme(&theme)
+                    .with_prompt(format!("Sign-in code emailed to {identifier}"))
+                    .interact_text()?;
+                auth_factor_token = Some(token.trim().to_string());
+            }
+            Err(e) => return Err(e).context(format!("Failed to log in as {identifier}")),
+        }
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn core::error::Error>> {
-    // Parse command line options and read the configuration file.
-    let opts = Opts::parse();
-    let config = match opts.command {
-        Command::Delete { config } => config,
+/// Log in to BlueSky and return the agent along with the DID of the logged in user.
+///
+/// The session saved by a previous run is resumed if possible, so that the app password is
+/// only used when the session cannot be refreshed anymore.
+async fn login(settings: &Settings, auth_factor_token: Option<&str>) -> Result<(Agent, Did)> {
+    let identifier = match settings.authentication.identifier() {
+        Some(identifier) => identifier,
+        None => keyring::identifier()?.context(
+            "No identifier: set authentication.identifier or BSKY_IDENTIFIER, \
+             or run the login command",
+        )?,
+    };
+    let session_file = match &settings.authentication.session_file {
+        Some(session_file) => PathBuf::from(session_file),
+        None => session::default_path(&identifier)?,
     };
-    let settings = Settings::from_file(&config)?;
+    let store = session::FileSessionStore::new(session_file);
+    let saved_session = store.load().unwrap_or_else(|e| {
+        eprintln!("Could not load the saved session: {e:#}");
+        None
+    });
+
+    // Never act on another account than the configured one, e.g. after the identifier changed.
+    let saved_session = saved_session.filter(|session| {
+        let belongs = session::belongs_to(session, &identifier);
+        if !belongs {
+            eprintln!(
+                "Ignoring the saved session of {}, which is not the configured account",
+                session.handle.as_str()
+            );
+        }
+        belongs
+    });
 
-    // Log in to BlueSky.
-    let agent = BskyAgent::builder().build().await?;
-    agent
-        .login(
-            settings.authentication.identifier,
-            settings.authentication.app_password,
-        )
-        .await?;
+    let agent = build_agent(store).await?;
+    if let Some(service) = &settings.authentication.service {
+        agent.configure_endpoint(service.clone());
+    }
+    let resumed = match saved_session {
+        Some(session) => {
+            // Talk to the PDS that issued the session, which may differ from the service.
+            if let Some(endpoint) = identity::session_pds(&session) {
+                agent.configure_endpoint(endpoint);
+            }
+            match agent.resume_session(session).await {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("Could not resume the saved session, logging in again: {e}");
+                    false
+                }
+            }
+        }
+        None => false,
+    };
+    if !resumed {
+        let (identifier, app_password) = credentials(settings)?;
+        let service = match &settings.authentication.service {
+            Some(service) => service.clone(),
+            None => {
+                agent.configure_endpoint(Config::default().endpoint);
+                resolve_service(&agent, &identifier).await
+            }
+        };
+        agent.configure_endpoint(service);
+        create_session(&agent, &identifier, &app_password, auth_factor_token).await?;
+    }
 
     // Get the DID of the logged in user (Decentralized Identifier).
     let did = agent
         .get_session()
         .await
-        .expect("could not get DID for the logged in user")
+        .context("Could not get the session of the logged in user")?
         .did
         .clone();
+    Ok((agent, did))
+}
 
-    // Get all posts from the user
-    let output = agent
-        .api
-        .app
-        .bsky
-        .feed
-        .get_author_feed(Parameters {
-            data: ParametersData {
-                actor: Did(did.clone()),
-                cursor: None,
-                filter: None,
-                include_pins: Some(false),
-                limit: None,
-            },
-            extra_data: ipld_core::ipld::Ipld::Null,
-        })
-        .await?;
-
-    // Collect the URIs of the records to delete.
-    let mut records_to_delete = vec![];
-    let cutoff_time =
-        Datetime::new((chrono::Utc::now() - settings.rules.delete.minimum_age).into());
-    for feed_view_post in &output.feed {
-        // Map the ATProtocol generic data into the BlueSky specific RecordData type.
-        let record = RecordData::try_from_unknown(feed_view_post.post.record.clone())?;
-        if record.created_at > cutoff_time {
-            // Skip posts that are too recent.
-            continue;
+/// Fetch the user's records and select the ones to delete along with the rule that selected them.
+async fn plan(
+    settings: &Settings,
+    agent: &Agent,
+    did: &Did,
+    page_size: u8,
+    report: &mut report::Report,
+) -> Result<Vec<(records::Candidate, String)>> {
+    // Get all posts and reposts from the user.
+    let mut candidates = match settings.source {
+        Source::Feed => records::fetch_author_feed(agent, did, page_size, report).await?,
+        Source::Repo => {
+            let mut candidates = records::list_repo_records(agent, page_size, report).await?;
+            // Engagement counts are only available from the AppView.
+            records::hydrate_views(agent, &mut candidates).await?;
+            candidates
         }
+    };
 
-        if feed_view_post.post.author.did == did {
-            records_to_delete.push(feed_view_post.post.uri.clone());
-        } else {
-            records_to_delete.push(
-                feed_view_post
-                    .post
-                    .viewer
-                    .as_ref()
-                    .expect("empty viewer for repost")
-                    .repost
-                    .as_ref()
-                    .expect("empty repost for viewer")
-                    .clone(),
-            );
+    // Get all likes from the user if they are to be deleted.
+    if settings.rules.likes.is_some() {
+        candidates.extend(records::list_likes(agent, page_size, report).await?);
+    }
+
+    // A record deleted twice in the same batch makes the whole batch fail.
+    let mut seen = HashSet::new();
+    candidates.retain(|candidate| seen.insert(candidate.uri.clone()));
+
+    // Collect the records to delete along with the rule that selected them.
+    let pinned_post = if settings.rules.keep.pinned {
+        records::pinned_post(agent, did).await?
+    } else {
+        None
+    };
+    let matcher = rules::Matcher::new(&settings.rules, did, &candidates, pinned_post)?;
+    Ok(matcher.select(candidates))
+}
+
+/// Archive and delete `records_to_delete`, recording the outcome of each one.
+///
+/// Stops at the first error with the `abort` policy.
+async fn archive_and_delete(
+    settings: &Settings,
+    agent: &Agent,
+    did: &Did,
+    records_to_delete: &[(records::Candidate, String)],
+    journal: &mut journal::Journal,
+    report: &mut report::Report,
+) -> Result<()> {
+    let mut archive = settings
+        .archive
+        .as_ref()
+        .map(|archive| archive::Archive::open(&archive.path))
+        .transpose()?;
+    let retries = match settings.errors.policy {
+        ErrorPolicy::RetryThenSkip => settings.errors.retries,
+        ErrorPolicy::Abort | ErrorPolicy::Skip => 0,
+    };
+    for batch in records_to_delete.chunks(deletion::APPLY_WRITES_LIMIT) {
+        // Only delete records that were archived successfully.
+        let mut uris = Vec::with_capacity(batch.len());
+        for (candidate, _) in batch {
+            if let Some(archive) = &mut archive {
+                if let Err(e) = archive.save(agent, did, candidate).await {
+                    report.skip(&candidate.uri, e)?;
+                    continue;
+                }
+            }
+            uris.push(candidate.uri.clone());
+        }
+
+        let outcomes =
+            deletion::delete_batch(agent, did, &uris, settings.errors.policy, retries).await;
+        for (uri, outcome) in uris.iter().zip(outcomes) {
+            journal.record(uri, &outcome)?;
+            match outcome {
+                deletion::Outcome::Deleted => report.succeed(uri),
+                deletion::Outcome::Failed(e) => report.fail(uri, &e),
+            }
+        }
+        if report.should_abort() {
+            break;
         }
     }
-    println!("About to delete {} records", records_to_delete.len());
+    Ok(())
+}
+
+async fn delete(yes: bool, auth_factor_token: Option<&str>, args: &DeleteArgs) -> Result<()> {
+    let settings = Settings::from_file(&args.config)?;
+    let (agent, did) = login(&settings, auth_factor_token).await?;
+    let journal_path = args
+        .journal
+        .clone()
+        .unwrap_or_else(|| format!("{}.journal", args.config));
+    let mut report = report::Report::new(settings.errors.policy);
+
+    let (records_to_delete, mut journal) = if args.resume {
+        let (journal, records_to_delete) = journal::Journal::resume(&journal_path)?;
+        println!("Resuming deletion of {} records", records_to_delete.len());
+        (records_to_delete, journal)
+    } else {
+        let records_to_delete = plan(&settings, &agent, &did, args.page_size, &mut report).await?;
+        if args.dry_run {
+            for (candidate, rule) in &records_to_delete {
+                println!(
+                    "{} ({}, {}) [{}] {}",
+                    candidate.uri,
+                    candidate.kind,
+                    candidate.created_at.as_str(),
+                    rule,
+                    candidate.summary()
+                );
+            }
+            println!("Would delete {} records", records_to_delete.len());
+            return Ok(());
+        }
+        println!("About to delete {} records", records_to_delete.len());
 
-    // Confirm deletion.
-    if !opts.yes {
-        let theme = ColorfulTheme::default();
-        let prompt = Confirm::with_theme(&theme).with_prompt("Do you want to proceed?");
-        if !prompt.interact()? {
+        // Confirm deletion.
+        if !confirm(yes)? {
             println!("Aborted.");
             return Ok(());
         }
+
+        let journal = journal::Journal::create(&journal_path, &records_to_delete)?;
+        (records_to_delete, journal)
+    };
+
+    // Archive and delete the records, printing a summary even if the run stops early.
+    let result = archive_and_delete(
+        &settings,
+        &agent,
+        &did,
+        &records_to_delete,
+        &mut journal,
+        &mut report,
+    )
+    .await;
+    report.print();
+    result?;
+    if report.should_abort() {
+        anyhow::bail!("Aborted after an error");
+    }
+    if settings.errors.fail_on_errors && report.has_errors() {
+        anyhow::bail!("Some records were skipped or could not be deleted");
+    }
+    Ok(())
+}
+
+async fn restore(
+    yes: bool,
+    auth_factor_token: Option<&str>,
+    config: &str,
+    uris: &[String],
+) -> Result<()> {
+    let settings = Settings::from_file(config)?;
+    let archive = settings
+        .archive
+        .as_ref()
+        .context(format!("No archive section in {config}"))?;
+
+    // Collect the archived posts to restore. Resumed runs may archive a post more than once.
+    let mut seen = HashSet::new();
+    let mut entries: Vec<_> = archive::read_entries(&archive.path)?
+        .into_iter()
+        .filter(|entry| entry.record.is_some() && entry.kind == records::Kind::Post)
+        .filter(|entry| uris.is_empty() || uris.contains(&entry.uri))
+        .filter(|entry| seen.insert(entry.uri.clone()))
+        .collect();
+    // Restore parents and quoted posts before the posts referencing them.
+    entries.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    println!("About to restore {} posts", entries.len());
+
+    // Confirm restoration.
+    if !confirm(yes)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    let (agent, did) = login(&settings, auth_factor_token).await?;
+    let mut failed = 0;
+    for entry in entries {
+        match archive::restore(&agent, &did, &archive.path, &entry).await {
+            Ok(true) => println!("Restored {}", entry.uri),
+            Ok(false) => println!("Skipping {}: it already exists", entry.uri),
+            Err(e) => {
+                eprintln!("Failed to restore {}: {e:#}", entry.uri);
+                failed += 1;
+            }
+        }
     }
 
-    // Delete the records.
-    for uri in records_to_delete {
-        agent.delete_record(uri).await?;
+    if failed > 0 {
+        anyhow::bail!("{failed} posts could not be restored");
+    }
+    Ok(())
+}
+
+/// Prompt for the user's credentials, verify them and store them in the keyring.
+async fn store_credentials(service: Option<String>, auth_factor_token: Option<&str>) -> Result<()> {
+    let theme = ColorfulTheme::default();
+    let identifier: String = Input::with_theme(&theme)
+        .with_prompt("Identifier")
+        .interact_text()?;
+    let app_password = Password::with_theme(&theme)
+        .with_prompt("App password")
+        .interact()?;
+
+    let agent = build_agent(MemorySessionStore::default()).await?;
+    let service = match service {
+        Some(service) => service,
+        None => resolve_service(&agent, &identifier).await,
+    };
+    agent.configure_endpoint(service);
+    create_session(&agent, &identifier, &app_password, auth_factor_token).await?;
+    keyring::store(&identifier, &app_password)?;
+    println!("Stored the credentials of {identifier} in the keyring");
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn core::error::Error>> {
+    // Parse command line options and run the command.
+    let opts = Opts::parse();
+    let auth_factor_token = opts.auth_factor_token.as_deref();
+    match opts.command {
+        Command::Delete(args) => delete(opts.yes, auth_factor_token, &args).await?,
+        Command::Login { service } => store_credentials(service, auth_factor_token).await?,
+        Command::Restore { config, uris } => {
+            restore(opts.yes, auth_factor_token, &config, &uris).await?
+        }
     }
 
     Ok(())
@@ -0,0 +1,146 @@
+use atrium_api::xrpc::{
+    http::{Method, Request, Response, StatusCode},
+    HttpClient, XrpcClient,
+};
+use atrium_xrpc_client::reqwest::ReqwestClient;
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Maximum number of retries for a request that failed with a transient error.
+const MAX_RETRIES: u32 = 6;
+
+/// Delay before the first retry. It doubles with every retry.
+const BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Maximum delay between retries, not counting waits for the rate limit to reset.
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// An HTTP client that respects the PDS rate limits and retries transient failures.
+///
+/// The `ratelimit-remaining` and `ratelimit-reset` response headers are used to wait for
+/// the rate limit window to reset before sending more requests. Rate limited responses and
+/// server errors are retried with exponential backoff and jitter, as are network errors of
+/// `GET` requests.
+pub struct RateLimitedClient {
+    inner: ReqwestClient,
+
+    /// Time before which no request should be sent.
+    blocked_until: Mutex<Option<SystemTime>>,
+}
+
+impl RateLimitedClient {
+    pub fn new(base_uri: impl AsRef<str>) -> Self {
+        Self {
+            inner: ReqwestClient::new(base_uri),
+            blocked_until: Mutex::new(None),
+        }
+    }
+
+    /// Wait until the rate limit window resets, if it was exhausted.
+    async fn wait_for_reset(&self) {
+        let blocked_until = *self.blocked_until.lock().expect("poisoned lock");
+        if let Some(wait) =
+            blocked_until.and_then(|until| until.duration_since(SystemTime::now()).ok())
+        {
+            eprintln!("Rate limit reached, waiting {}s", wait.as_secs());
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Record the rate limit state advertised by `response`.
+    ///
+    /// Returns the time at which the rate limit resets if it is exhausted.
+    fn update_rate_limit(&self, response: &Response<Vec<u8>>) -> Option<SystemTime> {
+        let header = |name: &str| -> Option<u64> {
+            response.headers().get(name)?.to_str().ok()?.parse().ok()
+        };
+        let exhausted = header("ratelimit-remaining") == Some(0)
+            || response.status() == StatusCode::TOO_MANY_REQUESTS;
+        let reset = header("ratelimit-reset").map(|reset| UNIX_EPOCH + Duration::from_secs(reset));
+        let blocked_until = reset.filter(|_| exhausted);
+        *self.blocked_until.lock().expect("poisoned lock") = blocked_until;
+        blocked_until
+    }
+}
+
+/// Delay before the given retry, with exponential backoff and full jitter.
+pub fn backoff(retry: u32) -> Duration {
+    let delay = BASE_DELAY.saturating_mul(1 << retry.min(16)).min(MAX_DELAY);
+    delay.mul_f64(fastrand::f64())
+}
+
+/// Copy `request` so that it can be sent again.
+fn clone_request(request: &Request<Vec<u8>>) -> Request<Vec<u8>> {
+    let mut clone = Request::new(request.body().clone());
+    *clone.method_mut() = request.method().clone();
+    *clone.uri_mut() = request.uri().clone();
+    *clone.version_mut() = request.version();
+    *clone.headers_mut() = request.headers().clone();
+    clone
+}
+
+/// Whether a `method` request that got a response with `status` may succeed if sent again.
+///
+/// Requests that are not `GET` may have been applied by the server even though the gateway
+/// failed, so they are only retried when the server says it refused them.
+fn is_transient(method: &Method, status: StatusCode) -> bool {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => true,
+        StatusCode::BAD_GATEWAY | StatusCode::GATEWAY_TIMEOUT => method == Method::GET,
+        _ => false,
+    }
+}
+
+impl HttpClient for RateLimitedClient {
+    async fn send_http(
+        &self,
+        request: Request<Vec<u8>>,
+    ) -> Result<Response<Vec<u8>>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut retry = 0;
+        loop {
+            self.wait_for_reset().await;
+            let result = self.inner.send_http(clone_request(&request)).await;
+            let reset = match &result {
+                Ok(response) if is_transient(request.method(), response.status()) => {
+                    self.update_rate_limit(response)
+                }
+                Ok(response) => {
+                    self.update_rate_limit(response);
+                    return result;
+                }
+                // A request that is not `GET` may have been applied before the connection
+                // failed, and sending it again could e.g. create a duplicate post.
+                Err(_) if request.method() != Method::GET => return result,
+                Err(_) => None,
+            };
+            if retry >= MAX_RETRIES {
+                return result;
+            }
+
+            // Wait for the rate limit to reset if the server said when it does, or back off.
+            if reset.is_none_or(|reset| reset <= SystemTime::now()) {
+                let delay = backoff(retry);
+                match &result {
+                    Ok(response) => eprintln!(
+                        "Request failed with {}, retrying in {}ms",
+                        response.status(),
+                        delay.as_millis()
+                    ),
+                    Err(e) => {
+                        eprintln!("Request failed ({e}), retrying in {}ms", delay.as_millis())
+                    }
+                }
+                tokio::time::sleep(delay).await;
+            }
+            retry += 1;
+        }
+    }
+}
+
+impl XrpcClient for RateLimitedClient {
+    fn base_uri(&self) -> String {
+        self.inner.base_uri()
+    }
+}
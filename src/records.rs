@@ -0,0 +1,417 @@
+use crate::report::Report;
+use crate::Agent;
+use anyhow::{Context, Result};
+use atrium_api::{
+    agent::store::SessionStore,
+    app::bsky::actor::{self, profile},
+    app::bsky::feed::{
+        defs::{FeedViewPostReasonRefs, PostView},
+        get_author_feed::{Parameters, ParametersData},
+        get_posts, like, post, repost,
+    },
+    com::atproto::repo::{get_record, list_records},
+    types::{
+        string::{AtIdentifier, Cid, Datetime, Did},
+        Collection, LimitedNonZeroU8, TryFromUnknown, Union, Unknown,
+    },
+    xrpc::{
+        error::{Error as XrpcError, XrpcErrorKind},
+        XrpcClient,
+    },
+};
+use bsky_sdk::{record::Record, BskyAgent};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The kind of a record considered for deletion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Post,
+    Repost,
+    Like,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Post => write!(f, "post"),
+            Kind::Repost => write!(f, "repost"),
+            Kind::Like => write!(f, "like"),
+        }
+    }
+}
+
+/// A record owned by the user that may be deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candidate {
+    /// URI of the record to delete.
+    pub uri: String,
+
+    /// CID of the record to delete, if known.
+    pub cid: Option<Cid>,
+
+    /// Kind of the record.
+    pub kind: Kind,
+
+    /// Creation time used to compute the age of the record. For reposts, this is the time
+    /// of the repost, not of the reposted post.
+    pub created_at: Datetime,
+
+    /// The record as stored in the user's repository, if known.
+    pub value: Option<Unknown>,
+
+    /// The post record, or the reposted post record for reposts, if known.
+    /// Always `None` for likes.
+    pub record: Option<post::RecordData>,
+
+    /// Hydrated view of the post (or of the reposted post), if known.
+    pub view: Option<PostView>,
+}
+
+impl Candidate {
+    /// First line of the post text, or an empty string if the text is unknown.
+    pub fn summary(&self) -> &str {
+        self.record
+            .as_ref()
+            .and_then(|record| record.text.lines().next())
+            .unwrap_or_default()
+    }
+}
+
+/// Convert a page size into the limit type used by the API.
+fn page_limit(page_size: u8) -> Result<LimitedNonZeroU8<100>> {
+    page_size
+        .try_into()
+        .map_err(|e| anyhow::anyhow!("Invalid page size {page_size}: {e}"))
+}
+
+/// Fetch the whole author feed for `did`, following the cursor until it is exhausted.
+///
+/// Posts of the user that cannot be decoded are reported as skipped. The repost records of
+/// reposts are looked up in the user's repository, since the feed only has the time the
+/// repost was indexed and may lack its URI.
+pub async fn fetch_author_feed(
+    agent: &Agent,
+    did: &Did,
+    page_size: u8,
+    report: &mut Report,
+) -> Result<Vec<Candidate>> {
+    let limit = page_limit(page_size)?;
+    let mut candidates = vec![];
+    let mut reposts = vec![];
+    let mut cursor = None;
+    loop {
+        let output = agent
+            .api
+            .app
+            .bsky
+            .feed
+            .get_author_feed(Parameters {
+                data: ParametersData {
+                    actor: AtIdentifier::Did(did.clone()),
+                    cursor,
+                    filter: None,
+                    include_pins: Some(false),
+                    limit: Some(limit),
+                },
+                extra_data: ipld_core::ipld::Ipld::Null,
+            })
+            .await
+            .context("Failed to fetch author feed")?;
+
+        for feed_view_post in output.data.feed {
+            // Map the ATProtocol generic data into the BlueSky specific RecordData type.
+            let record = post::RecordData::try_from_unknown(feed_view_post.post.record.clone());
+
+            // Reposts are told apart by their reason, since users may repost their own posts.
+            // Deleting a repost does not need the reposted post, which may be malformed.
+            if let Some(Union::Refs(FeedViewPostReasonRefs::ReasonRepost(reason))) =
+                &feed_view_post.reason
+            {
+                let indexed_at = reason.indexed_at.clone();
+                reposts.push((record.ok(), indexed_at, feed_view_post.data.post));
+                continue;
+            }
+            if feed_view_post.post.author.did != *did {
+                eprintln!(
+                    "Skipping {}: neither a post nor a repost of the user",
+                    feed_view_post.post.uri
+                );
+                continue;
+            }
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    report.skip(&feed_view_post.post.uri, e.into())?;
+                    continue;
+                }
+            };
+            candidates.push(Candidate {
+                uri: feed_view_post.post.uri.clone(),
+                cid: Some(feed_view_post.post.cid.clone()),
+                kind: Kind::Post,
+                value: Some(feed_view_post.post.record.clone()),
+                created_at: record.created_at.clone(),
+                record: Some(record),
+                view: Some(feed_view_post.data.post),
+            });
+        }
+
+        match output.data.cursor {
+            Some(next) if !next.is_empty() => cursor = Some(next),
+            _ => break,
+        }
+    }
+
+    if !reposts.is_empty() {
+        let mut repost_records = repost_records_by_subject(agent, page_size).await?;
+        for (record, indexed_at, view) in reposts {
+            let candidate = match repost_records.remove(&view.uri) {
+                Some((repost, data)) => Candidate {
+                    uri: repost.uri,
+                    cid: Some(repost.cid),
+                    kind: Kind::Repost,
+                    value: Some(repost.value),
+                    created_at: data.created_at,
+                    record,
+                    view: Some(view),
+                },
+                // Fall back to the viewer state and the time the repost was indexed.
+                None => {
+                    let uri = view
+                        .viewer
+                        .as_ref()
+                        .and_then(|viewer| viewer.repost.clone());
+                    let Some(uri) = uri else {
+                        eprintln!("Skipping repost of {}: no repost record found", view.uri);
+                        continue;
+                    };
+                    Candidate {
+                        uri,
+                        cid: None,
+                        kind: Kind::Repost,
+                        value: None,
+                        created_at: indexed_at,
+                        record,
+                        view: Some(view),
+                    }
+                }
+            };
+            candidates.push(candidate);
+        }
+    }
+    Ok(candidates)
+}
+
+/// Map the URI of each post reposted by the user to the repost record and its decoded data.
+async fn repost_records_by_subject(
+    agent: &Agent,
+    page_size: u8,
+) -> Result<HashMap<String, (list_records::RecordData, repost::RecordData)>> {
+    let reposts = list_all::<repost::Record, _, _>(agent, page_size)
+        .await
+        .context("Failed to list repost records")?;
+    Ok(reposts
+        .into_iter()
+        .filter_map(|record| {
+            let data = repost::RecordData::try_from_unknown(record.data.value.clone()).ok()?;
+            Some((data.subject.uri.clone(), (record.data, data)))
+        })
+        .collect())
+}
+
+/// List every record of the collection `R` in the user's repository.
+async fn list_all<R, T, S>(
+    agent: &BskyAgent<T, S>,
+    page_size: u8,
+) -> Result<Vec<list_records::Record>>
+where
+    R: Record<T, S>,
+    T: XrpcClient + Send + Sync,
+    S: SessionStore + Send + Sync,
+{
+    let limit = page_limit(page_size)?;
+    let mut records = vec![];
+    let mut cursor = None;
+    loop {
+        let output = R::list(agent, cursor, Some(limit)).await?;
+        records.extend(output.data.records);
+        match output.data.cursor {
+            Some(next) if !next.is_empty() => cursor = Some(next),
+            _ => break,
+        }
+    }
+    Ok(records)
+}
+
+/// List every post and repost record in the user's repository.
+///
+/// Unlike the author feed, this reads the repository directly from the PDS, so it also
+/// finds records the AppView does not return (e.g. taken down or un-indexed posts).
+/// Records that cannot be decoded are reported as skipped.
+pub async fn list_repo_records(
+    agent: &Agent,
+    page_size: u8,
+    report: &mut Report,
+) -> Result<Vec<Candidate>> {
+    let mut candidates = vec![];
+
+    let posts = list_all::<post::Record, _, _>(agent, page_size)
+        .await
+        .context("Failed to list post records")?;
+    for record in posts {
+        let data = match post::RecordData::try_from_unknown(record.data.value.clone()) {
+            Ok(data) => data,
+            Err(e) => {
+                report.skip(&record.data.uri, e.into())?;
+                continue;
+            }
+        };
+        candidates.push(Candidate {
+            uri: record.data.uri,
+            cid: Some(record.data.cid),
+            kind: Kind::Post,
+            value: Some(record.data.value),
+            created_at: data.created_at.clone(),
+            record: Some(data),
+            view: None,
+        });
+    }
+
+    let reposts = list_all::<repost::Record, _, _>(agent, page_size)
+        .await
+        .context("Failed to list repost records")?;
+    for record in reposts {
+        let data = match repost::RecordData::try_from_unknown(record.data.value.clone()) {
+            Ok(data) => data,
+            Err(e) => {
+                report.skip(&record.data.uri, e.into())?;
+                continue;
+            }
+        };
+        candidates.push(Candidate {
+            uri: record.data.uri,
+            cid: Some(record.data.cid),
+            kind: Kind::Repost,
+            value: Some(record.data.value),
+            created_at: data.created_at,
+            record: None,
+            view: None,
+        });
+    }
+
+    Ok(candidates)
+}
+
+/// List every like record in the user's repository.
+///
+/// Records that cannot be decoded are reported as skipped.
+pub async fn list_likes(
+    agent: &Agent,
+    page_size: u8,
+    report: &mut Report,
+) -> Result<Vec<Candidate>> {
+    let mut candidates = vec![];
+    let likes = list_all::<like::Record, _, _>(agent, page_size)
+        .await
+        .context("Failed to list like records")?;
+    for record in likes {
+        let data = match like::RecordData::try_from_unknown(record.data.value.clone()) {
+            Ok(data) => data,
+            Err(e) => {
+                report.skip(&record.data.uri, e.into())?;
+                continue;
+            }
+        };
+        candidates.push(Candidate {
+            uri: record.data.uri,
+            cid: Some(record.data.cid),
+            kind: Kind::Like,
+            value: Some(record.data.value),
+            created_at: data.created_at,
+            record: None,
+            view: None,
+        });
+    }
+    Ok(candidates)
+}
+
+/// Maximum number of URIs accepted by `app.bsky.feed.getPosts`.
+const GET_POSTS_LIMIT: usize = 25;
+
+/// Fill in the post views of posts read from the repository using the AppView.
+///
+/// Posts unknown to the AppView are left without a view.
+pub async fn hydrate_views(agent: &Agent, candidates: &mut [Candidate]) -> Result<()> {
+    let uris: Vec<_> = candidates
+        .iter()
+        .filter(|candidate| candidate.kind == Kind::Post && candidate.view.is_none())
+        .map(|candidate| candidate.uri.clone())
+        .collect();
+
+    let mut views = HashMap::new();
+    for chunk in uris.chunks(GET_POSTS_LIMIT) {
+        let output = agent
+            .api
+            .app
+            .bsky
+            .feed
+            .get_posts(
+                get_posts::ParametersData {
+                    uris: chunk.to_vec(),
+                }
+                .into(),
+            )
+            .await
+            .context("Failed to fetch post views")?;
+        views.extend(
+            output
+                .data
+                .posts
+                .into_iter()
+                .map(|view| (view.uri.clone(), view)),
+        );
+    }
+
+    for candidate in candidates {
+        if candidate.view.is_none() {
+            candidate.view = views.remove(&candidate.uri);
+        }
+    }
+    Ok(())
+}
+
+/// Get the URI of the post pinned to the user's profile, if any.
+pub async fn pinned_post(agent: &Agent, did: &Did) -> Result<Option<String>> {
+    let output = agent
+        .api
+        .com
+        .atproto
+        .repo
+        .get_record(
+            get_record::ParametersData {
+                cid: None,
+                collection: actor::Profile::nsid(),
+                repo: AtIdentifier::Did(did.clone()),
+                rkey: String::from("self"),
+            }
+            .into(),
+        )
+        .await;
+    let output = match output {
+        Ok(output) => output,
+        // Accounts without a profile record have nothing pinned.
+        Err(XrpcError::XrpcResponse(error))
+            if matches!(
+                error.error,
+                Some(XrpcErrorKind::Custom(get_record::Error::RecordNotFound(_)))
+            ) =>
+        {
+            return Ok(None)
+        }
+        Err(error) => return Err(error).context("Failed to fetch profile record"),
+    };
+    let profile = profile::RecordData::try_from_unknown(output.data.value)?;
+    Ok(profile.pinned_post.map(|pinned_post| pinned_post.data.uri))
+}
@@ -0,0 +1,74 @@
+use crate::settings::ErrorPolicy;
+use anyhow::Result;
+
+/// Tracks the outcome of every record of a run and applies the configured error policy.
+pub struct Report {
+    policy: ErrorPolicy,
+    succeeded: Vec<String>,
+    skipped: Vec<(String, String)>,
+    failed: Vec<(String, String)>,
+}
+
+impl Report {
+    pub fn new(policy: ErrorPolicy) -> Self {
+        Self {
+            policy,
+            succeeded: vec![],
+            skipped: vec![],
+            failed: vec![],
+        }
+    }
+
+    /// Record that `uri` was deleted.
+    pub fn succeed(&mut self, uri: &str) {
+        self.succeeded.push(uri.to_string());
+    }
+
+    /// Record that `uri` will not be deleted because of `error`.
+    ///
+    /// Also returns the error if the policy is to abort.
+    pub fn skip(&mut self, uri: &str, error: anyhow::Error) -> Result<()> {
+        self.skipped.push((uri.to_string(), format!("{error:#}")));
+        if self.policy == ErrorPolicy::Abort {
+            return Err(error.context(format!("Failed to process {uri}")));
+        }
+        eprintln!("Skipping {uri}: {error:#}");
+        Ok(())
+    }
+
+    /// Record that deleting `uri` failed with `error`.
+    pub fn fail(&mut self, uri: &str, error: &anyhow::Error) {
+        eprintln!("Failed to delete {uri}: {error:#}");
+        self.failed.push((uri.to_string(), format!("{error:#}")));
+    }
+
+    /// Whether the run should stop because of an error.
+    pub fn should_abort(&self) -> bool {
+        self.policy == ErrorPolicy::Abort && self.has_errors()
+    }
+
+    /// Whether any record was skipped or failed to be deleted.
+    pub fn has_errors(&self) -> bool {
+        !self.skipped.is_empty() || !self.failed.is_empty()
+    }
+
+    /// Print a summary of the run.
+    pub fn print(&self) {
+        println!("Deleted {} records", self.succeeded.len());
+        for uri in &self.succeeded {
+            println!("  {uri}");
+        }
+        if !self.skipped.is_empty() {
+            println!("Skipped {} records", self.skipped.len());
+            for (uri, reason) in &self.skipped {
+                println!("  {uri}: {reason}");
+            }
+        }
+        if !self.failed.is_empty() {
+            println!("Failed to delete {} records", self.failed.len());
+            for (uri, reason) in &self.failed {
+                println!("  {uri}: {reason}");
+            }
+        }
+    }
+}
@@ -0,0 +1,722 @@
+use crate::records::{Candidate, Kind};
+use crate::settings::{Age, Rules, ThreadPolicy};
+use anyhow::{Context, Result};
+use atrium_api::{
+    app::bsky::{
+        feed::post::{RecordData, RecordEmbedRefs},
+        richtext::facet::MainFeaturesItem,
+    },
+    types::{
+        string::{Datetime, Did},
+        Union,
+    },
+};
+use regex::{Regex, RegexBuilder};
+use std::collections::{HashMap, HashSet};
+
+/// The kinds of posts that can have their own deletion rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PostKind {
+    Post,
+    Reply,
+    SelfReply,
+    Quote,
+    Repost,
+}
+
+impl PostKind {
+    fn of(candidate: &Candidate) -> Self {
+        if candidate.kind == Kind::Repost {
+            return PostKind::Repost;
+        }
+        let Some(record) = &candidate.record else {
+            return PostKind::Post;
+        };
+        if let Some(reply) = &record.reply {
+            return if authority(&reply.parent.uri) == authority(&candidate.uri) {
+                PostKind::SelfReply
+            } else {
+                PostKind::Reply
+            };
+        }
+        match &record.embed {
+            Some(Union::Refs(
+                RecordEmbedRefs::AppBskyEmbedRecordMain(_)
+                | RecordEmbedRefs::AppBskyEmbedRecordWithMediaMain(_),
+            )) => PostKind::Quote,
+            _ => PostKind::Post,
+        }
+    }
+}
+
+/// The repository part of an `at://` URI.
+fn authority(uri: &str) -> Option<&str> {
+    uri.strip_prefix("at://")?.split('/').next()
+}
+
+/// Evaluates the configured rules against candidate records.
+pub struct Matcher<'a> {
+    rules: &'a Rules,
+    /// Rule name and cutoff time for each kind of post. A `None` cutoff means never delete.
+    cutoffs: HashMap<PostKind, (&'static str, Option<Datetime>)>,
+    likes_cutoff_time: Option<Datetime>,
+    /// Posts created before this time are kept.
+    window_start: Option<Datetime>,
+    /// Posts created at or after this time are kept.
+    window_end: Option<Datetime>,
+    hashtags: Vec<String>,
+    keywords: Vec<String>,
+    patterns: Vec<Regex>,
+    /// URIs of records that are always kept.
+    protected: HashSet<String>,
+    /// URIs of the user's likes of their own posts and reposts, kept with `rules.keep.self_liked`.
+    self_likes: HashSet<String>,
+    /// Thread of each post, keyed by URI, if threads are decided on as a whole.
+    threads: HashMap<String, String>,
+    /// Creation time used for the age of the posts of each thread.
+    thread_times: HashMap<String, Datetime>,
+}
+
+/// Compute the creation time before which a record is old enough to be deleted.
+fn cutoff(minimum_age: chrono::Duration) -> Datetime {
+    Datetime::new((chrono::Utc::now() - minimum_age).into())
+}
+
+impl<'a> Matcher<'a> {
+    /// Create a matcher for `rules` over the `candidates` of the user `did` that never
+    /// selects `pinned_post`.
+    pub fn new(
+        rules: &'a Rules,
+        did: &Did,
+        candidates: &[Candidate],
+        pinned_post: Option<String>,
+    ) -> Result<Self> {
+        let patterns = rules
+            .keep
+            .patterns
+            .iter()
+            .map(|pattern| {
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .context(format!(
+                        "Invalid pattern {pattern:?} in rules.keep.patterns"
+                    ))
+            })
+            .collect::<Result<_>>()?;
+
+        let delete = &rules.delete;
+        let cutoffs = [
+            (PostKind::Post, "rules.delete.posts", delete.posts),
+            (PostKind::Reply, "rules.delete.replies", delete.replies),
+            (
+                PostKind::SelfReply,
+                "rules.delete.self_replies",
+                delete.self_replies,
+            ),
+            (PostKind::Quote, "rules.delete.quotes", delete.quotes),
+            (PostKind::Repost, "rules.delete.reposts", delete.reposts),
+        ]
+        .into_iter()
+        .map(|(kind, rule, age)| {
+            let cutoff_time = match age {
+                None => Some(cutoff(delete.minimum_age)),
+                Some(Age::Minimum(minimum_age)) => Some(cutoff(minimum_age)),
+                Some(Age::Never) => None,
+            };
+            let rule = if age.is_some() {
+                rule
+            } else {
+                "rules.delete.minimum_age"
+            };
+            (kind, (rule, cutoff_time))
+        })
+        .collect();
+
+        let window_start = delete
+            .maximum_age
+            .map(cutoff)
+            .into_iter()
+            .chain(delete.after.clone())
+            .max();
+        if delete
+            .maximum_age
+            .is_some_and(|maximum_age| maximum_age <= delete.minimum_age)
+        {
+            anyhow::bail!("rules.delete.maximum_age must be greater than rules.delete.minimum_age");
+        }
+        if let (Some(start), Some(end)) = (&window_start, &delete.before) {
+            if start >= end {
+                anyhow::bail!(
+                    "rules.delete.before must be later than rules.delete.after and \
+                     rules.delete.maximum_age, otherwise no post can be deleted"
+                );
+            }
+        }
+
+        let self_likes = if rules.keep.self_liked {
+            candidates
+                .iter()
+                .filter_map(|candidate| candidate.view.as_ref()?.viewer.as_ref()?.like.clone())
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        // Only self-threads are grouped: replies in conversations started by other users
+        // are decided on independently.
+        let threads: HashMap<_, _> = match rules.threads {
+            ThreadPolicy::Independent => HashMap::new(),
+            ThreadPolicy::ByRootAge | ThreadPolicy::ByNewestAge => candidates
+                .iter()
+                .filter(|candidate| candidate.kind == Kind::Post)
+                .map(|candidate| (candidate.uri.clone(), thread_root(candidate)))
+                .filter(|(_, root)| authority(root) == Some(did.as_str()))
+                .map(|(uri, root)| (uri, root.to_string()))
+                .collect(),
+        };
+        let mut thread_times: HashMap<String, Datetime> = HashMap::new();
+        for candidate in candidates {
+            let Some(root) = threads.get(&candidate.uri) else {
+                continue;
+            };
+            let time = thread_times
+                .entry(root.clone())
+                .or_insert_with(|| candidate.created_at.clone());
+            let replace = match rules.threads {
+                // The root is the oldest post of its thread, unless it is not known.
+                ThreadPolicy::ByRootAge => candidate.uri == *root || candidate.created_at < *time,
+                ThreadPolicy::ByNewestAge => candidate.created_at > *time,
+                ThreadPolicy::Independent => false,
+            };
+            if replace {
+                *time = candidate.created_at.clone();
+            }
+        }
+
+        Ok(Self {
+            rules,
+            cutoffs,
+            window_start,
+            window_end: delete.before.clone(),
+            likes_cutoff_time: rules.likes.as_ref().map(|likes| cutoff(likes.minimum_age)),
+            hashtags: rules
+                .keep
+                .hashtags
+                .iter()
+                .map(|hashtag| hashtag.trim_start_matches('#').to_lowercase())
+                .collect(),
+            keywords: rules
+                .keep
+                .keywords
+                .iter()
+                .map(|keyword| keyword.to_lowercase())
+                .collect(),
+            patterns,
+            protected: pinned_post
+                .into_iter()
+                .chain(latest(candidates, Kind::Post, rules.keep.latest))
+                .chain(latest(candidates, Kind::Repost, rules.keep.latest_reposts))
+                .collect(),
+            self_likes,
+            threads,
+            thread_times,
+        })
+    }
+
+    /// Select the candidates to delete along with the rule that selected them.
+    ///
+    /// Posts of a thread decided on as a whole are only selected if all of them match.
+    pub fn select(&self, candidates: Vec<Candidate>) -> Vec<(Candidate, String)> {
+        let matches: Vec<_> = candidates
+            .into_iter()
+            .map(|candidate| {
+                let rule = self.matching_rule(&candidate);
+                (candidate, rule)
+            })
+            .collect();
+
+        let kept_threads: HashSet<_> = matches
+            .iter()
+            .filter(|(_, rule)| rule.is_none())
+            .filter_map(|(candidate, _)| self.threads.get(&candidate.uri))
+            .cloned()
+            .collect();
+        matches
+            .into_iter()
+            .filter(|(candidate, _)| {
+                self.threads
+                    .get(&candidate.uri)
+                    .is_none_or(|thread| !kept_threads.contains(thread))
+            })
+            .filter_map(|(candidate, rule)| Some((candidate, rule?.to_string())))
+            .collect()
+    }
+
+    /// Return the name of the rule that selects `candidate` for deletion, if any.
+    fn matching_rule(&self, candidate: &Candidate) -> Option<&'static str> {
+        if candidate.kind == Kind::Like {
+            let likes_cutoff_time = self.likes_cutoff_time.as_ref()?;
+            if candidate.created_at > *likes_cutoff_time {
+                // Skip likes that are too recent.
+                return None;
+            }
+            if self.self_likes.contains(&candidate.uri) {
+                // Never delete the likes marking posts to keep.
+                return None;
+            }
+            return Some("rules.likes.minimum_age");
+        }
+
+        // Posts of a thread decided on as a whole share the creation time of the thread.
+        let created_at = self
+            .threads
+            .get(&candidate.uri)
+            .and_then(|thread| self.thread_times.get(thread))
+            .unwrap_or(&candidate.created_at);
+
+        // Kinds of posts configured as `never` are always kept.
+        let (rule, cutoff_time) = &self.cutoffs[&PostKind::of(candidate)];
+        if created_at > cutoff_time.as_ref()? {
+            // Skip posts that are too recent.
+            return None;
+        }
+
+        if self
+            .window_start
+            .as_ref()
+            .is_some_and(|start| created_at < start)
+            || self
+                .window_end
+                .as_ref()
+                .is_some_and(|end| created_at >= end)
+        {
+            // Skip posts outside of the configured age band.
+            return None;
+        }
+
+        if self.protected.contains(&candidate.uri) {
+            // Never delete the pinned post or the most recent posts.
+            return None;
+        }
+
+        if self.rules.keep.self_liked && is_self_liked(candidate) {
+            return None;
+        }
+
+        if candidate.kind == Kind::Post
+            && (self.is_popular(candidate) || self.is_protected(candidate))
+        {
+            return None;
+        }
+
+        Some(rule)
+    }
+
+    /// Whether the post reached any of the engagement thresholds in `rules.keep`.
+    fn is_popular(&self, candidate: &Candidate) -> bool {
+        let Some(view) = &candidate.view else {
+            return false;
+        };
+        let keep = &self.rules.keep;
+        let reached = |count: Option<i64>, threshold: Option<i64>| match threshold {
+            Some(threshold) => count.unwrap_or_default() >= threshold,
+            None => false,
+        };
+        reached(view.like_count, keep.min_likes)
+            || reached(view.repost_count, keep.min_reposts)
+            || reached(view.reply_count, keep.min_replies)
+            || reached(view.quote_count, keep.min_quotes)
+    }
+
+    /// Whether the post text or tags contain any of the protected hashtags, keywords or patterns.
+    fn is_protected(&self, candidate: &Candidate) -> bool {
+        let Some(record) = &candidate.record else {
+            return false;
+        };
+
+        if !self.hashtags.is_empty()
+            && post_hashtags(record).any(|tag| self.hashtags.contains(&tag.to_lowercase()))
+        {
+            return true;
+        }
+
+        let text = record.text.to_lowercase();
+        self.keywords.iter().any(|keyword| text.contains(keyword))
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.is_match(&record.text))
+    }
+}
+
+/// Whether the user liked the post, or the reposted post for reposts.
+fn is_self_liked(candidate: &Candidate) -> bool {
+    candidate
+        .view
+        .as_ref()
+        .and_then(|view| view.viewer.as_ref())
+        .is_some_and(|viewer| viewer.like.is_some())
+}
+
+/// URI of the root of the thread `candidate` belongs to, which may be the candidate itself.
+fn thread_root(candidate: &Candidate) -> &str {
+    candidate
+        .record
+        .as_ref()
+        .and_then(|record| record.reply.as_ref())
+        .map_or(&candidate.uri, |reply| &reply.root.uri)
+}
+
+/// URIs of the `count` most recent candidates of the given kind.
+fn latest(candidates: &[Candidate], kind: Kind, count: Option<usize>) -> Vec<String> {
+    let mut candidates: Vec<_> = candidates
+        .iter()
+        .filter(|candidate| candidate.kind == kind)
+        .collect();
+    candidates.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    candidates
+        .into_iter()
+        .take(count.unwrap_or_default())
+        .map(|candidate| candidate.uri.clone())
+        .collect()
+}
+
+/// Hashtags of a post, from both its rich text facets and its additional tags.
+fn post_hashtags(record: &RecordData) -> impl Iterator<Item = &str> {
+    let facet_tags = record
+        .facets
+        .iter()
+        .flatten()
+        .flat_map(|facet| &facet.features)
+        .filter_map(|feature| match feature {
+            Union::Refs(MainFeaturesItem::Tag(tag)) => Some(tag.tag.as_str()),
+            _ => None,
+        });
+    let extra_tags = record.tags.iter().flatten().map(String::as_str);
+    facet_tags.chain(extra_tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{Delete, Keep};
+    use atrium_api::{app::bsky::feed::post::ReplyRefData, com::atproto::repo::strong_ref};
+
+    const USER: &str = "did:plc:user";
+    const OTHER: &str = "did:plc:other";
+    const CID: &str = "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm";
+
+    fn uri(did: &str, rkey: &str) -> String {
+        format!("at://{did}/app.bsky.feed.post/{rkey}")
+    }
+
+    fn days_ago(days: i64) -> Datetime {
+        Datetime::new((chrono::Utc::now() - chrono::Duration::days(days)).into())
+    }
+
+    fn strong_ref(uri: &str) -> strong_ref::Main {
+        strong_ref::MainData {
+            cid: CID.parse().unwrap(),
+            uri: uri.to_string(),
+        }
+        .into()
+    }
+
+    /// A post of the user created `age` days ago, replying to `parent` in the thread `root`.
+    fn post(rkey: &str, age: i64, text: &str, reply: Option<(&str, &str)>) -> Candidate {
+        let created_at = days_ago(age);
+        let record = RecordData {
+            created_at: created_at.clone(),
+            embed: None,
+            entities: None,
+            facets: None,
+            labels: None,
+            langs: None,
+            reply: reply.map(|(root, parent)| {
+                ReplyRefData {
+                    parent: strong_ref(parent),
+                    root: strong_ref(root),
+                }
+                .into()
+            }),
+            tags: None,
+            text: text.to_string(),
+        };
+        Candidate {
+            uri: uri(USER, rkey),
+            cid: None,
+            kind: Kind::Post,
+            value: None,
+            created_at,
+            record: Some(record),
+            view: None,
+        }
+    }
+
+    fn rules(threads: ThreadPolicy) -> Rules {
+        Rules {
+            delete: Delete {
+                minimum_age: chrono::Duration::days(30),
+                posts: None,
+                replies: None,
+                self_replies: None,
+                quotes: None,
+                reposts: None,
+                maximum_age: None,
+                before: None,
+                after: None,
+            },
+            likes: None,
+            keep: Keep {
+                pinned: false,
+                keywords: vec![String::from("keep")],
+                ..Keep::default()
+            },
+            threads,
+        }
+    }
+
+    /// URIs selected for deletion among `candidates`.
+    fn select(rules: &Rules, candidates: Vec<Candidate>) -> Vec<String> {
+        let did = Did::new(USER.to_string()).unwrap();
+        let matcher = Matcher::new(rules, &did, &candidates, None).unwrap();
+        let mut uris: Vec<_> = matcher
+            .select(candidates)
+            .into_iter()
+            .map(|(candidate, _)| candidate.uri)
+            .collect();
+        uris.sort();
+        uris
+    }
+
+    /// An old self-thread root with a recent reply.
+    fn self_thread() -> Vec<Candidate> {
+        let root = uri(USER, "a");
+        vec![
+            post("a", 40, "root", None),
+            post("b", 10, "reply", Some((&root, &root))),
+        ]
+    }
+
+    #[test]
+    fn independent_decides_on_each_post() {
+        let rules = rules(ThreadPolicy::Independent);
+        assert_eq!(select(&rules, self_thread()), vec![uri(USER, "a")]);
+    }
+
+    #[test]
+    fn by_root_age_deletes_recent_replies_of_old_threads() {
+        let rules = rules(ThreadPolicy::ByRootAge);
+        assert_eq!(
+            select(&rules, self_thread()),
+            vec![uri(USER, "a"), uri(USER, "b")]
+        );
+    }
+
+    #[test]
+    fn by_newest_age_keeps_old_posts_of_recent_threads() {
+        let rules = rules(ThreadPolicy::ByNewestAge);
+        assert!(select(&rules, self_thread()).is_empty());
+    }
+
+    #[test]
+    fn kept_post_keeps_its_whole_thread() {
+        let rules = rules(ThreadPolicy::ByRootAge);
+        let root = uri(USER, "a");
+        let candidates = vec![
+            post("a", 40, "root", None),
+            post("b", 35, "reply", Some((&root, &root))),
+            post("c", 32, "keep this one", Some((&root, &uri(USER, "b")))),
+            post("d", 50, "unrelated", None),
+        ];
+        assert_eq!(select(&rules, candidates), vec![uri(USER, "d")]);
+    }
+
+    #[test]
+    fn replies_to_other_users_are_not_grouped() {
+        let rules = rules(ThreadPolicy::ByRootAge);
+        let root = uri(OTHER, "z");
+        let candidates = vec![
+            post("a", 40, "old reply", Some((&root, &root))),
+            post("b", 10, "recent reply", Some((&root, &root))),
+            post("c", 35, "keep this reply", Some((&root, &root))),
+        ];
+        assert_eq!(select(&rules, candidates), vec![uri(USER, "a")]);
+    }
+
+    /// A repost made `age` days ago of a post of another user created `post_age` days ago.
+    fn repost(rkey: &str, age: i64, post_age: i64) -> Candidate {
+        let mut reposted = post(rkey, post_age, "reposted", None);
+        reposted.uri = format!("at://{USER}/app.bsky.feed.repost/{rkey}");
+        reposted.kind = Kind::Repost;
+        reposted.created_at = days_ago(age);
+        reposted
+    }
+
+    #[test]
+    fn latest_reposts_are_ordered_by_repost_time() {
+        let mut rules = rules(ThreadPolicy::Independent);
+        rules.keep.latest_reposts = Some(1);
+        let candidates = vec![repost("a", 35, 100), repost("b", 50, 40)];
+        assert_eq!(
+            select(&rules, candidates),
+            vec![format!("at://{USER}/app.bsky.feed.repost/b")]
+        );
+    }
+
+    /// A post of the user created `age` days ago quoting a post of another user.
+    fn quote(rkey: &str, age: i64) -> Candidate {
+        let mut quote = post(rkey, age, "quote", None);
+        if let Some(record) = &mut quote.record {
+            record.embed = Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedRecordMain(
+                Box::new(
+                    atrium_api::app::bsky::embed::record::MainData {
+                        record: strong_ref(&uri(OTHER, "q")),
+                    }
+                    .into(),
+                ),
+            )));
+        }
+        quote
+    }
+
+    #[test]
+    fn post_kinds_are_classified() {
+        let own = uri(USER, "a");
+        let other = uri(OTHER, "z");
+        let kinds = [
+            (post("a", 1, "post", None), PostKind::Post),
+            (
+                post("b", 1, "reply", Some((&other, &other))),
+                PostKind::Reply,
+            ),
+            (
+                post("c", 1, "self-reply", Some((&own, &own))),
+                PostKind::SelfReply,
+            ),
+            // Replying to the user in someone else's thread is still a self-reply.
+            (
+                post("d", 1, "self-reply", Some((&other, &own))),
+                PostKind::SelfReply,
+            ),
+            (quote("e", 1), PostKind::Quote),
+            (repost("f", 1, 1), PostKind::Repost),
+        ];
+        for (candidate, kind) in kinds {
+            assert_eq!(PostKind::of(&candidate), kind, "{}", candidate.summary());
+        }
+    }
+
+    #[test]
+    fn kind_set_to_never_is_always_kept() {
+        let mut rules = rules(ThreadPolicy::Independent);
+        rules.delete.replies = Some(Age::Never);
+        let other = uri(OTHER, "z");
+        let candidates = vec![
+            post("a", 100, "post", None),
+            post("b", 100, "reply", Some((&other, &other))),
+        ];
+        assert_eq!(select(&rules, candidates), vec![uri(USER, "a")]);
+    }
+
+    #[test]
+    fn kind_without_its_own_age_falls_back_to_minimum_age() {
+        let mut rules = rules(ThreadPolicy::Independent);
+        rules.delete.quotes = Some(Age::Minimum(chrono::Duration::days(5)));
+        let other = uri(OTHER, "z");
+        let candidates = vec![
+            quote("a", 10),
+            post("b", 10, "recent reply", Some((&other, &other))),
+            post("c", 40, "old reply", Some((&other, &other))),
+        ];
+        let did = Did::new(USER.to_string()).unwrap();
+        let matcher = Matcher::new(&rules, &did, &candidates, None).unwrap();
+        let mut selected: Vec<_> = matcher
+            .select(candidates)
+            .into_iter()
+            .map(|(candidate, rule)| (candidate.uri, rule))
+            .collect();
+        selected.sort();
+        assert_eq!(
+            selected,
+            vec![
+                (uri(USER, "a"), String::from("rules.delete.quotes")),
+                (uri(USER, "c"), String::from("rules.delete.minimum_age")),
+            ]
+        );
+    }
+
+    /// A top-level post of the user created at `created_at`.
+    fn post_at(rkey: &str, created_at: &Datetime) -> Candidate {
+        let mut candidate = post(rkey, 0, "post", None);
+        candidate.created_at = created_at.clone();
+        if let Some(record) = &mut candidate.record {
+            record.created_at = created_at.clone();
+        }
+        candidate
+    }
+
+    #[test]
+    fn posts_created_at_before_are_kept() {
+        let mut rules = rules(ThreadPolicy::Independent);
+        let before = days_ago(40);
+        rules.delete.before = Some(before.clone());
+        let candidates = vec![post_at("a", &before), post("b", 50, "older", None)];
+        assert_eq!(select(&rules, candidates), vec![uri(USER, "b")]);
+    }
+
+    #[test]
+    fn posts_created_before_after_are_kept() {
+        let mut rules = rules(ThreadPolicy::Independent);
+        let after = days_ago(60);
+        rules.delete.after = Some(after.clone());
+        let candidates = vec![
+            post("a", 70, "older", None),
+            post_at("b", &after),
+            post("c", 50, "newer", None),
+        ];
+        assert_eq!(
+            select(&rules, candidates),
+            vec![uri(USER, "b"), uri(USER, "c")]
+        );
+    }
+
+    #[test]
+    fn maximum_age_and_after_use_the_latest_start() {
+        let candidates = || {
+            vec![
+                post("a", 80, "80 days", None),
+                post("b", 50, "50 days", None),
+                post("c", 40, "40 days", None),
+            ]
+        };
+
+        // `after` is later than `maximum_age`.
+        let mut rules = rules(ThreadPolicy::Independent);
+        rules.delete.maximum_age = Some(chrono::Duration::days(100));
+        rules.delete.after = Some(days_ago(60));
+        assert_eq!(
+            select(&rules, candidates()),
+            vec![uri(USER, "b"), uri(USER, "c")]
+        );
+
+        // `maximum_age` is later than `after`.
+        rules.delete.maximum_age = Some(chrono::Duration::days(45));
+        assert_eq!(select(&rules, candidates()), vec![uri(USER, "c")]);
+    }
+
+    #[test]
+    fn empty_age_band_is_rejected() {
+        let did = Did::new(USER.to_string()).unwrap();
+
+        let mut maximum_below_minimum = rules(ThreadPolicy::Independent);
+        maximum_below_minimum.delete.maximum_age = Some(chrono::Duration::days(20));
+        assert!(Matcher::new(&maximum_below_minimum, &did, &[], None).is_err());
+
+        let mut after_past_before = rules(ThreadPolicy::Independent);
+        after_past_before.delete.after = Some(days_ago(40));
+        after_past_before.delete.before = Some(days_ago(50));
+        assert!(Matcher::new(&after_past_before, &did, &[], None).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use atrium_api::agent::{
+    store::{MemorySessionStore, SessionStore},
+    Session,
+};
+use std::path::PathBuf;
+
+/// A session store that keeps the session in a file, so it can be resumed by the next run.
+///
+/// The file is rewritten whenever the session changes, including when its tokens are
+/// refreshed in the middle of a run.
+pub struct FileSessionStore {
+    path: PathBuf,
+    memory: MemorySessionStore,
+}
+
+impl FileSessionStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            memory: MemorySessionStore::default(),
+        }
+    }
+
+    /// Read the session saved in the file, if any.
+    pub fn load(&self) -> Result<Option<Session>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let session = std::fs::read_to_string(&self.path).context(format!(
+            "Failed to read session file {}",
+            self.path.display()
+        ))?;
+        serde_json::from_str(&session)
+            .map(Some)
+            .context(format!("Invalid session file {}", self.path.display()))
+    }
+
+    /// Write `session` to the file, readable by the user only.
+    fn save(&self, session: &Session) -> Result<()> {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options.open(&self.path).context(format!(
+            "Failed to create session file {}",
+            self.path.display()
+        ))?;
+        serde_json::to_writer(file, session).context(format!(
+            "Failed to write session file {}",
+            self.path.display()
+        ))
+    }
+}
+
+/// Default file of the session of `identifier`, in the per-user state directory.
+///
+/// The directory is `$XDG_STATE_HOME/bsky-deleter`, or `~/.local/state/bsky-deleter` when
+/// `XDG_STATE_HOME` is not set, and is created readable by the user only if missing.
+pub fn default_path(identifier: &str) -> Result<PathBuf> {
+    let state_home = match std::env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(
+            std::env::var_os("HOME").context("Neither XDG_STATE_HOME nor HOME is set")?,
+        )
+        .join(".local/state"),
+    };
+    let dir = state_home.join(env!("CARGO_PKG_NAME"));
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&dir).context(format!(
+        "Failed to create state directory {}",
+        dir.display()
+    ))?;
+
+    // Identifiers are DIDs, handles or emails; keep whatever else out of the file name.
+    let name: String = identifier
+        .trim_start_matches('@')
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '.' | '-' | '_' | '@' | ':' => c,
+            _ => '_',
+        })
+        .collect();
+    Ok(dir.join(format!("{name}.session")))
+}
+
+/// Whether `session` is the session of the account `identifier`, a DID, handle or email.
+pub fn belongs_to(session: &Session, identifier: &str) -> bool {
+    let identifier = identifier.trim_start_matches('@');
+    session.did.as_str() == identifier
+        || session.handle.as_str().eq_ignore_ascii_case(identifier)
+        || session
+            .email
+            .as_deref()
+            .is_some_and(|email| email.eq_ignore_ascii_case(identifier))
+}
+
+impl SessionStore for FileSessionStore {
+    async fn get_session(&self) -> Option<Session> {
+        self.memory.get_session().await
+    }
+
+    async fn set_session(&self, session: Session) {
+        if let Err(e) = self.save(&session) {
+            eprintln!("Could not save the session: {e:#}");
+        }
+        self.memory.set_session(session).await;
+    }
+
+    async fn clear_session(&self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("Could not remove session file {}: {e}", self.path.display());
+            }
+        }
+        self.memory.clear_session().await;
+    }
+}
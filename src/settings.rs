@@ -0,0 +1,345 @@
+use anyhow::{Context, Result};
+use atrium_api::types::string::Datetime;
+use config::{Config, File};
+use serde::Deserialize;
+
+/// Environment variable overriding `authentication.identifier`.
+const IDENTIFIER_ENV: &str = "BSKY_IDENTIFIER";
+
+/// Environment variable overriding the configured app password.
+const APP_PASSWORD_ENV: &str = "BSKY_APP_PASSWORD";
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Authentication {
+    /// URL of the PDS or entryway to log in to, e.g. `https://bsky.social`. Defaults to the
+    /// PDS found in the DID document of the identifier.
+    pub service: Option<String>,
+
+    /// BlueSky identifier. Overridden by the `BSKY_IDENTIFIER` environment variable.
+    pub identifier: Option<String>,
+
+    /// BlueSky app password from <https://bsky.app/settings/app-password>.
+    pub app_password: Option<String>,
+
+    /// Name of an environment variable holding the app password.
+    pub app_password_env: Option<String>,
+
+    /// Path of a file holding the app password.
+    pub app_password_file: Option<String>,
+
+    /// Shell command printing the app password, e.g. from a secret manager.
+    pub app_password_command: Option<String>,
+
+    /// File where the session is saved between runs. Defaults to `<identifier>.session` in
+    /// `$XDG_STATE_HOME/bsky-deleter`, or `~/.local/state/bsky-deleter`.
+    pub session_file: Option<String>,
+}
+
+impl Authentication {
+    /// The identifier from `BSKY_IDENTIFIER` or the configuration file, if any.
+    pub fn identifier(&self) -> Option<String> {
+        std::env::var(IDENTIFIER_ENV)
+            .ok()
+            .or_else(|| self.identifier.clone())
+    }
+
+    /// The app password from `BSKY_APP_PASSWORD` or the one source configured in the file, if any.
+    pub fn app_password(&self) -> Result<Option<String>> {
+        if let Ok(app_password) = std::env::var(APP_PASSWORD_ENV) {
+            return Ok(Some(app_password));
+        }
+
+        let sources = [
+            self.app_password.is_some(),
+            self.app_password_env.is_some(),
+            self.app_password_file.is_some(),
+            self.app_password_command.is_some(),
+        ];
+        if sources.into_iter().filter(|&configured| configured).count() > 1 {
+            anyhow::bail!(
+                "Only one of authentication.app_password, app_password_env, app_password_file \
+                 and app_password_command can be set"
+            );
+        }
+
+        let app_password = if let Some(app_password) = &self.app_password {
+            app_password.clone()
+        } else if let Some(name) = &self.app_password_env {
+            std::env::var(name).context(format!("Failed to read app password from ${name}"))?
+        } else if let Some(path) = &self.app_password_file {
+            let app_password = std::fs::read_to_string(path)
+                .context(format!("Failed to read app password from {path}"))?;
+            app_password.trim_end_matches(['\r', '\n']).to_string()
+        } else if let Some(command) = &self.app_password_command {
+            run_password_command(command)?
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(app_password))
+    }
+}
+
+/// Run `command` with the shell and return the first line it prints.
+fn run_password_command(command: &str) -> Result<String> {
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .context(format!("Failed to run app password command {command:?}"))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "App password command {command:?} failed with {}",
+            output.status
+        );
+    }
+    let stdout =
+        String::from_utf8(output.stdout).context("App password command printed invalid UTF-8")?;
+    Ok(stdout.lines().next().unwrap_or_default().to_string())
+}
+
+/// How old a record must be to be deleted, or `never` to always keep it.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(try_from = "String")]
+pub enum Age {
+    Never,
+    Minimum(chrono::Duration),
+}
+
+impl TryFrom<String> for Age {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value.eq_ignore_ascii_case("never") {
+            return Ok(Age::Never);
+        }
+        duration_str::parse_chrono(value).map(Age::Minimum)
+    }
+}
+
+/// Deserialize an optional human readable duration such as `2y`.
+fn deserialize_option_duration<'de, D>(
+    deserializer: D,
+) -> Result<Option<chrono::Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| duration_str::parse_chrono(value).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Delete {
+    /// Minimum age of a post to be considered for deletion.
+    #[serde(deserialize_with = "duration_str::deserialize_duration_chrono")]
+    pub minimum_age: chrono::Duration,
+
+    /// Minimum age of top-level posts. Defaults to `minimum_age`.
+    pub posts: Option<Age>,
+
+    /// Minimum age of replies to other users. Defaults to `minimum_age`.
+    pub replies: Option<Age>,
+
+    /// Minimum age of replies to own posts. Defaults to `minimum_age`.
+    pub self_replies: Option<Age>,
+
+    /// Minimum age of quote posts. Defaults to `minimum_age`.
+    pub quotes: Option<Age>,
+
+    /// Minimum age of reposts. Defaults to `minimum_age`.
+    pub reposts: Option<Age>,
+
+    /// Maximum age of a post to be considered for deletion. Older posts are kept.
+    #[serde(default, deserialize_with = "deserialize_option_duration")]
+    pub maximum_age: Option<chrono::Duration>,
+
+    /// Only delete posts created before this RFC 3339 date.
+    pub before: Option<Datetime>,
+
+    /// Only delete posts created after this RFC 3339 date.
+    pub after: Option<Datetime>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Likes {
+    /// Minimum age of a like to be considered for deletion.
+    #[serde(deserialize_with = "duration_str::deserialize_duration_chrono")]
+    pub minimum_age: chrono::Duration,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct Keep {
+    /// Keep the post pinned to the profile.
+    pub pinned: bool,
+
+    /// Keep this many of the most recent posts regardless of their age.
+    pub latest: Option<usize>,
+
+    /// Keep this many of the most recent reposts regardless of their age.
+    pub latest_reposts: Option<usize>,
+
+    /// Keep posts with at least this many likes.
+    pub min_likes: Option<i64>,
+
+    /// Keep posts with at least this many reposts.
+    pub min_reposts: Option<i64>,
+
+    /// Keep posts with at least this many replies.
+    pub min_replies: Option<i64>,
+
+    /// Keep posts with at least this many quotes.
+    pub min_quotes: Option<i64>,
+
+    /// Keep posts tagged with any of these hashtags (case-insensitive, leading `#` optional).
+    pub hashtags: Vec<String>,
+
+    /// Keep posts whose text contains any of these keywords (case-insensitive).
+    pub keywords: Vec<String>,
+
+    /// Keep posts whose text matches any of these regular expressions (case-insensitive).
+    pub patterns: Vec<String>,
+
+    /// Keep posts and reposts liked by the user, along with the likes themselves.
+    pub self_liked: bool,
+}
+
+impl Default for Keep {
+    fn default() -> Self {
+        Self {
+            pinned: true,
+            latest: None,
+            latest_reposts: None,
+            min_likes: None,
+            min_reposts: None,
+            min_replies: None,
+            min_quotes: None,
+            hashtags: vec![],
+            keywords: vec![],
+            patterns: vec![],
+            self_liked: false,
+        }
+    }
+}
+
+/// How to decide on posts that belong to the same thread.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThreadPolicy {
+    /// Decide on each post on its own.
+    #[default]
+    Independent,
+
+    /// Use the age of the thread root for every post of the thread.
+    ByRootAge,
+
+    /// Use the age of the newest post of the thread for every post of the thread.
+    ByNewestAge,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Rules {
+    /// When to delete posts.
+    pub delete: Delete,
+
+    /// When to delete likes. Likes are kept if this section is missing.
+    pub likes: Option<Likes>,
+
+    /// Which posts to keep regardless of their age.
+    #[serde(default)]
+    pub keep: Keep,
+
+    /// How to decide on posts of the same thread. Threads other than `independent` are
+    /// deleted or kept as a whole.
+    #[serde(default)]
+    pub threads: ThreadPolicy,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Archive {
+    /// Directory where records are saved before being deleted.
+    pub path: String,
+}
+
+/// What to do when a record cannot be processed or deleted.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorPolicy {
+    /// Stop at the first error.
+    #[default]
+    Abort,
+
+    /// Skip the record and continue.
+    Skip,
+
+    /// Retry deleting the record, then skip it if it still fails.
+    RetryThenSkip,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct Errors {
+    /// What to do when a record cannot be processed or deleted.
+    pub policy: ErrorPolicy,
+
+    /// Number of retries with the `retry-then-skip` policy.
+    pub retries: u32,
+
+    /// Exit with a non-zero code if any record was skipped or failed to be deleted.
+    pub fail_on_errors: bool,
+}
+
+impl Default for Errors {
+    fn default() -> Self {
+        Self {
+            policy: ErrorPolicy::default(),
+            retries: 3,
+            fail_on_errors: false,
+        }
+    }
+}
+
+/// Where to read the user's posts and reposts from.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Source {
+    /// The author feed served by the AppView (`app.bsky.feed.getAuthorFeed`).
+    #[default]
+    Feed,
+
+    /// The records stored in the user's repository (`com.atproto.repo.listRecords`).
+    Repo,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Settings {
+    /// Authentication settings. Credentials stored by the `login` command are used if missing.
+    #[serde(default)]
+    pub authentication: Authentication,
+
+    /// Where to read records from.
+    #[serde(default)]
+    pub source: Source,
+
+    /// Rules for deleting or keeping posts.
+    pub rules: Rules,
+
+    /// Archive settings. Records are not archived if this section is missing.
+    pub archive: Option<Archive>,
+
+    /// Error handling settings.
+    #[serde(default)]
+    pub errors: Errors,
+}
+
+impl Settings {
+    pub fn from_file(path: &str) -> Result<Self> {
+        Config::builder()
+            .add_source(File::with_name(path))
+            .build()
+            .context(format!("Failed to build config from {path}"))?
+            .try_deserialize()
+            .context(format!("Failed to deserialize config from {path}"))
+    }
+}